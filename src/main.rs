@@ -1,10 +1,16 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::error::Error;
 use std::fs;
 use std::io::{self, Write};
+use std::path::PathBuf;
 use log::{info, error};
 use env_logger;
 use chrono::Local;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::Rng;
+use regex::Regex;
+use sha2::{Digest, Sha256};
 
 /// Represents a single rule containing an intent, associated patterns, and possible responses.
 #[derive(Deserialize, Debug)]
@@ -12,6 +18,33 @@ struct Rule {
     intent: String,
     patterns: Vec<String>,
     responses: Vec<String>,
+    /// Intents that must have been `session.last_intent` for this rule to match at all,
+    /// e.g. a "confirm" rule that only makes sense right after "order_food". `None` means
+    /// the rule is context-free and can match regardless of what came before.
+    #[serde(default)]
+    context_before: Option<Vec<String>>,
+    /// Whether matching this rule updates `session.last_intent` to its own intent.
+    /// Defaults to `true` so existing rule files keep working unmodified.
+    #[serde(default = "default_sets_context")]
+    sets_context: bool,
+    /// Optional per-response weights, parallel to `responses`, for weighted random
+    /// selection. `None` (or a length mismatch) falls back to a uniform pick.
+    #[serde(default)]
+    response_weights: Option<Vec<u32>>,
+}
+
+/// Default value for `Rule::sets_context` when the field is absent from the rule JSON.
+fn default_sets_context() -> bool {
+    true
+}
+
+/// A binary/media file attached during a session, fingerprinted so it isn't
+/// re-read or duplicated if attached again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+struct Attachment {
+    path: String,
+    mime: String,
+    hash: String,
 }
 
 /// Manages the state of a user session, including user details and conversation history.
@@ -21,6 +54,7 @@ struct Session {
     user_name: String,
     last_intent: Option<String>,
     conversation_history: Vec<String>,
+    attachments: Vec<Attachment>,
 }
 
 impl Session {
@@ -31,8 +65,206 @@ impl Session {
             user_name: user_name.to_string(),
             last_intent: None,
             conversation_history: Vec::new(),
+            attachments: Vec::new(),
+        }
+    }
+}
+
+/// Manages named sessions persisted as JSON files on disk, so a conversation
+/// can be saved, listed, and resumed across runs of the chatbot.
+struct SessionStore {
+    dir: PathBuf,
+}
+
+/// Checks that a session name is safe to use as a filename component, since it
+/// comes straight from chat input (`save session <name>` / `load session
+/// <name>`). Rejects anything containing a path separator or `..`, which would
+/// otherwise let a session name read or write a file outside `sessions/`.
+///
+/// # Arguments
+///
+/// * `name` - The session name to validate.
+///
+/// # Returns
+///
+/// * `Result<(), Box<dyn Error>>` - `Ok(())` if `name` is a bare alphanumeric
+///   (plus `-`/`_`) identifier, or an error describing what's wrong.
+fn validate_session_name(name: &str) -> Result<(), Box<dyn Error>> {
+    let valid = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid session name '{}': only letters, digits, '-', and '_' are allowed",
+            name
+        )
+        .into())
+    }
+}
+
+impl SessionStore {
+    /// Opens (creating if necessary) the directory that holds saved sessions.
+    ///
+    /// # Arguments
+    ///
+    /// * `dir` - Path to the directory sessions are stored in.
+    ///
+    /// # Returns
+    ///
+    /// * `io::Result<Self>` - The store, or an error if the directory couldn't be created.
+    fn new(dir: &str) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+        Ok(SessionStore { dir: PathBuf::from(dir) })
+    }
+
+    /// Builds the on-disk path for a named session.
+    fn path_for(&self, name: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", name))
+    }
+
+    /// Serializes and writes a session under the given name.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name to save the session under.
+    /// * `session` - The session to persist.
+    fn save(&self, name: &str, session: &Session) -> Result<(), Box<dyn Error>> {
+        validate_session_name(name)?;
+        let data = serde_json::to_string_pretty(session)?;
+        fs::write(self.path_for(name), data)?;
+        Ok(())
+    }
+
+    /// Reads and deserializes a previously saved session.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - The name the session was saved under.
+    fn load(&self, name: &str) -> Result<Session, Box<dyn Error>> {
+        validate_session_name(name)?;
+        let data = fs::read_to_string(self.path_for(name))?;
+        let session: Session = serde_json::from_str(&data)?;
+        Ok(session)
+    }
+
+    /// Lists the names of all saved sessions, sorted alphabetically.
+    fn list(&self) -> Result<Vec<String>, Box<dyn Error>> {
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                if let Some(stem) = path.file_stem() {
+                    names.push(stem.to_string_lossy().to_string());
+                }
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Finds the name of the most recently modified saved session, if any.
+    ///
+    /// Used on startup to auto-resume where the user left off.
+    fn most_recent(&self) -> Result<Option<String>, Box<dyn Error>> {
+        let mut newest: Option<(std::time::SystemTime, String)> = None;
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let path = entry.path();
+            if path.extension().is_some_and(|ext| ext == "json") {
+                let modified = entry.metadata()?.modified()?;
+                let name = path.file_stem().unwrap().to_string_lossy().to_string();
+                if newest.as_ref().is_none_or(|(time, _)| modified > *time) {
+                    newest = Some((modified, name));
+                }
+            }
+        }
+        Ok(newest.map(|(_, name)| name))
+    }
+}
+
+/// A rule pattern compiled into a regex, paired with the rule it belongs to.
+///
+/// Compiling patterns once at load time (rather than on every turn) keeps
+/// `match_rule` cheap even as the rule set grows.
+struct CompiledPattern<'a> {
+    regex: Regex,
+    rule: &'a Rule,
+}
+
+/// Compiles rule patterns that use actual regex syntax into case-insensitive
+/// regexes.
+///
+/// Plain literal phrases (no regex metacharacters) are skipped here on purpose:
+/// two literal patterns like `"need help"` and `"need help immediately"` are
+/// both trivially valid regexes, so letting them into this tier would make
+/// `match_rule` return whichever happens to sit first in the rule list rather
+/// than the better-fitting one. They're left for the token-overlap scoring
+/// pass (see `score_rule`) instead, which picks the best fit regardless of
+/// list order. Patterns with metacharacters that still fail to compile are
+/// also skipped here; they simply won't match via either tier.
+///
+/// # Arguments
+///
+/// * `rules` - The loaded rules to compile patterns for.
+///
+/// # Returns
+///
+/// * `Vec<CompiledPattern>` - One entry per pattern that compiled successfully.
+fn compile_patterns(rules: &[Rule]) -> Vec<CompiledPattern<'_>> {
+    let mut compiled = Vec::new();
+    for rule in rules {
+        for pattern in &rule.patterns {
+            if !has_regex_metacharacters(pattern) {
+                continue;
+            }
+            if let Ok(regex) = Regex::new(&format!("(?i){}", pattern)) {
+                compiled.push(CompiledPattern { regex, rule });
+            }
         }
     }
+    compiled
+}
+
+/// Whether a pattern uses actual regex syntax (capture groups, wildcards,
+/// anchors, alternation, etc.) rather than being a plain literal phrase.
+///
+/// # Arguments
+///
+/// * `pattern` - The rule pattern to inspect.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the pattern contains a regex metacharacter.
+fn has_regex_metacharacters(pattern: &str) -> bool {
+    pattern.contains(['\\', '^', '$', '.', '|', '?', '*', '+', '(', ')', '[', ']', '{', '}'])
+}
+
+/// Reflects first/second-person pronouns in a captured fragment (ELIZA-style),
+/// e.g. "i am sad" -> "you are sad", so the fragment reads naturally when
+/// echoed back inside a response template.
+///
+/// # Arguments
+///
+/// * `fragment` - The raw text captured by a regex group.
+///
+/// # Returns
+///
+/// * `String` - The fragment with reflected tokens, whitespace-rejoined.
+fn reflect_fragment(fragment: &str) -> String {
+    fragment
+        .split_whitespace()
+        .map(|token| match token.to_lowercase().as_str() {
+            "i" => "you".to_string(),
+            "my" => "your".to_string(),
+            "am" => "are".to_string(),
+            "you" => "i".to_string(),
+            "your" => "my".to_string(),
+            "me" => "you".to_string(),
+            _ => token.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 /// Entry point of the chatbot application.
@@ -46,14 +278,36 @@ fn main() -> Result<(), Box<dyn Error>> {
 
     // Load chatbot rules from the JSON file.
     let mut rules = load_rules_from_json(rules_path)?;
+    let mut compiled_patterns = compile_patterns(&rules);
 
     // Initialize a user session with default ID and name.
     let user_id = "user123";
     let user_name = "User";
-    let mut session = Session::new(user_id, user_name);
+
+    // Open the session store and auto-resume the most recently modified session,
+    // falling back to a fresh one if none exist yet.
+    let session_store = SessionStore::new("sessions")?;
+    let mut session_name = session_store.most_recent()?;
+    let mut session = match &session_name {
+        Some(name) => match session_store.load(name) {
+            Ok(session) => {
+                info!("Resumed session '{}'.", name);
+                session
+            }
+            Err(e) => {
+                error!("Failed to resume session '{}': {}", name, e);
+                session_name = None;
+                Session::new(user_id, user_name)
+            }
+        },
+        None => Session::new(user_id, user_name),
+    };
 
     // Welcome message to the user.
     println!("Welcome to Rust Chatbot! Type 'exit' to quit.");
+    if let Some(name) = &session_name {
+        println!("Chatbot: Resumed session '{}'.", name);
+    }
 
     // Start the main interaction loop.
     loop {
@@ -69,6 +323,9 @@ fn main() -> Result<(), Box<dyn Error>> {
         // Handle the 'exit' command to terminate the chatbot.
         if input_text.eq_ignore_ascii_case("exit") {
             println!("Chatbot: Goodbye!");
+            if session_name.is_none() {
+                println!("Chatbot: (Tip: this session wasn't saved — use 'save session <name>' to keep it.)");
+            }
             info!("User exited the chat.");
             break;
         }
@@ -78,6 +335,7 @@ fn main() -> Result<(), Box<dyn Error>> {
             match reload_rules(rules_path) {
                 Ok(new_rules) => {
                     rules = new_rules;
+                    compiled_patterns = compile_patterns(&rules);
                     println!("Chatbot: Rules reloaded successfully.");
                     info!("Rules reloaded.");
                 },
@@ -95,18 +353,95 @@ fn main() -> Result<(), Box<dyn Error>> {
             continue; // Restart the loop after listing intents.
         }
 
+        // Handle 'save session <name>' to persist the current session under a name.
+        else if let Some(rest) = strip_prefix_ci(input_text, "save session ") {
+            let name = rest.trim().to_string();
+            if name.is_empty() {
+                println!("Chatbot: Please provide a name, e.g. 'save session my-chat'.");
+            } else {
+                match session_store.save(&name, &session) {
+                    Ok(()) => {
+                        session_name = Some(name.clone());
+                        println!("Chatbot: Session saved as '{}'.", name);
+                        info!("Session saved as '{}'.", name);
+                    }
+                    Err(e) => {
+                        println!("Chatbot: Failed to save session: {}", e);
+                        error!("Failed to save session '{}': {}", name, e);
+                    }
+                }
+            }
+            continue;
+        }
+
+        // Handle 'load session <name>' to resume a previously saved session.
+        else if let Some(rest) = strip_prefix_ci(input_text, "load session ") {
+            let name = rest.trim().to_string();
+            match session_store.load(&name) {
+                Ok(loaded) => {
+                    session = loaded;
+                    session_name = Some(name.clone());
+                    println!("Chatbot: Loaded session '{}'.", name);
+                    info!("Session loaded: '{}'.", name);
+                }
+                Err(e) => {
+                    println!("Chatbot: Failed to load session '{}': {}", name, e);
+                    error!("Failed to load session '{}': {}", name, e);
+                }
+            }
+            continue;
+        }
+
+        // Handle 'list sessions' to show every session saved to disk.
+        else if input_text.eq_ignore_ascii_case("list sessions") {
+            match session_store.list() {
+                Ok(names) if names.is_empty() => println!("Chatbot: No saved sessions yet."),
+                Ok(names) => {
+                    println!("Chatbot: Saved sessions:");
+                    for name in names {
+                        println!("- {}", name);
+                    }
+                }
+                Err(e) => println!("Chatbot: Failed to list sessions: {}", e),
+            }
+            continue;
+        }
+
+        // Handle 'new session' to start a fresh, unnamed conversation.
+        else if input_text.eq_ignore_ascii_case("new session") {
+            session = Session::new(user_id, user_name);
+            session_name = None;
+            println!("Chatbot: Started a new session.");
+            info!("Started a new session.");
+            continue;
+        }
+
+        // Pull in any `attach <path>` command or inline `@path` tokens before matching.
+        let augmented_input = assemble_attachments(input_text, &mut session);
+
         // Process the user's input to determine the appropriate response.
-        let cleaned_input = clean_input(input_text); // Normalize the input.
-        let rule = match_rule(&cleaned_input, &rules); // Attempt to match an intent.
+        let cleaned_input = clean_input(&augmented_input); // Normalize the input.
+        let matched = match_rule(
+            &cleaned_input,
+            &rules,
+            &compiled_patterns,
+            session.last_intent.as_deref(),
+        ); // Attempt to match an intent, honoring the dialog context so far.
 
         // Generate the chatbot's response based on the matched intent.
-        let response = if let Some(rule) = rule {
-            session.last_intent = Some(rule.intent.clone()); // Update session with the last intent.
-            generate_response(rule, &session) // Generate a dynamic response.
+        let response = if let Some((rule, captures)) = matched {
+            if rule.sets_context {
+                session.last_intent = Some(rule.intent.clone()); // Update session with the last intent.
+            }
+            generate_response(rule, &session, &captures) // Generate a dynamic response.
         } else {
             "I'm sorry, I didn't understand that. Could you please rephrase?".to_string()
         };
 
+        // Record both sides of the exchange (including any assembled attachments) in history.
+        session.conversation_history.push(format!("You: {}", augmented_input));
+        session.conversation_history.push(format!("Chatbot: {}", response));
+
         // Display the chatbot's response to the user.
         println!("Chatbot: {}", response);
 
@@ -158,44 +493,347 @@ fn clean_input(input: &str) -> String {
     input.to_lowercase().trim().to_string()
 }
 
+/// Case-insensitively strips a prefix off a string, returning the (original-case)
+/// remainder. Used to parse parameterized commands like `save session <name>`.
+///
+/// # Arguments
+///
+/// * `input` - The raw string to check.
+/// * `prefix` - The prefix to match case-insensitively.
+///
+/// # Returns
+///
+/// * `Option<&str>` - The remainder of `input` after the prefix, or `None` if it
+///   doesn't start with `prefix`.
+fn strip_prefix_ci<'a>(input: &'a str, prefix: &str) -> Option<&'a str> {
+    if input.len() >= prefix.len() && input[..prefix.len()].eq_ignore_ascii_case(prefix) {
+        Some(&input[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+/// Pulls in any `attach <path>` command or inline `@path` tokens before intent
+/// matching runs, so a user can ask about the contents of a file in the same turn.
+///
+/// An `attach <path>` command replaces the whole message with the assembled
+/// attachment (there's no other text worth matching against). Inline `@path`
+/// tokens are left in place and the assembled attachment is appended, so the
+/// rest of the message still reaches `match_rule`.
+///
+/// # Arguments
+///
+/// * `input` - The raw user input, possibly containing `attach <path>` or `@path`.
+/// * `session` - The session to record new binary attachments on.
+///
+/// # Returns
+///
+/// * `String` - The input with attachments folded in, ready for `clean_input`.
+fn assemble_attachments(input: &str, session: &mut Session) -> String {
+    if let Some(rest) = strip_prefix_ci(input, "attach ") {
+        let path = rest.trim();
+        return match attach_file(path, session) {
+            Ok(note) => note,
+            Err(e) => {
+                error!("Failed to attach '{}': {}", path, e);
+                format!("[Failed to attach '{}': {}]", path, e)
+            }
+        };
+    }
+
+    let inline_paths: Vec<&str> = input.split_whitespace().filter_map(|token| token.strip_prefix('@')).collect();
+    if inline_paths.is_empty() {
+        return input.to_string();
+    }
+
+    let mut message = input.to_string();
+    for path in inline_paths {
+        let note = match attach_file(path, session) {
+            Ok(note) => note,
+            Err(e) => {
+                error!("Failed to attach '{}': {}", path, e);
+                format!("[Failed to attach '{}': {}]", path, e)
+            }
+        };
+        message.push('\n');
+        message.push_str(&note);
+    }
+    message
+}
+
+/// Reads or fingerprints a single attached file.
+///
+/// Text files are read and returned inline so their contents reach `match_rule`.
+/// Everything else is fingerprinted (MIME type + sha256 hash of its bytes) and
+/// recorded on `session.attachments`; a file whose hash is already recorded
+/// isn't re-read or duplicated, it's just noted as already cached.
+///
+/// # Arguments
+///
+/// * `path` - Path to the file to attach.
+/// * `session` - The session to record new binary attachments on.
+///
+/// # Returns
+///
+/// * `Result<String, Box<dyn Error>>` - A line describing the attachment.
+fn attach_file(path: &str, session: &mut Session) -> Result<String, Box<dyn Error>> {
+    let mime = mime_guess::from_path(path).first_or_octet_stream();
+
+    if mime.essence_str().starts_with("text/") {
+        let contents = fs::read_to_string(path)?;
+        return Ok(format!("[Attached file '{}']\n{}", path, contents));
+    }
+
+    let bytes = fs::read(path)?;
+    let hash = format!("{:x}", Sha256::digest(&bytes));
+
+    if let Some(cached) = session.attachments.iter().find(|a| a.hash == hash) {
+        return Ok(format!(
+            "[Attached '{}' ({}) — already cached as '{}', sha256:{}]",
+            path, mime, cached.path, hash
+        ));
+    }
+
+    session.attachments.push(Attachment {
+        path: path.to_string(),
+        mime: mime.to_string(),
+        hash: hash.clone(),
+    });
+    Ok(format!("[Attached '{}' ({}, sha256:{})]", path, mime, hash))
+}
+
+/// Minimum score a rule must reach in the scoring fallback pass (see `score_rule`)
+/// to be considered a match. Tune this up to require closer phrasing, or down to
+/// be more forgiving of noisy input.
+const MATCH_THRESHOLD: f64 = 0.5;
+
 /// Attempts to match the user's input to a defined intent.
 ///
+/// Tries each compiled regex pattern first, so rules like `"i am (.*)"` can
+/// capture a fragment for reflection; only patterns with actual regex syntax
+/// reach this tier (see `compile_patterns`). Falls back to a token-overlap
+/// scoring pass (see `score_rule`) for plain literal patterns, so the
+/// best-fitting rule wins regardless of where it sits in the rule list.
+///
+/// Rules with `context_before` are gated by `last_intent`: they're skipped
+/// entirely unless `last_intent` is one of the listed intents. Among rules
+/// that do match, a contextual rule (one with `context_before`) is preferred
+/// over a context-free one *regardless of which tier each matched in*, so a
+/// dialog state machine like "order_food" -> "confirm" takes priority over
+/// an unrelated catch-all pattern even when the catch-all is a regex hit and
+/// the contextual rule only clears the scoring threshold.
+///
 /// # Arguments
 ///
 /// * `user_input` - A string slice containing the normalized user input.
-/// * `rules` - A slice of `Rule` structs to match against.
+/// * `rules` - A slice of `Rule` structs to match against in the scoring pass.
+/// * `compiled_patterns` - Patterns pre-compiled into regexes via `compile_patterns`.
+/// * `last_intent` - The previous turn's matched intent, if any.
 ///
 /// # Returns
 ///
-/// * `Option<&Rule>` - A reference to the matched `Rule` or `None` if no match is found.
-fn match_rule<'a>(user_input: &str, rules: &'a [Rule]) -> Option<&'a Rule> {
-    for rule in rules {
-        for pattern in &rule.patterns {
-            if user_input.contains(&pattern.to_lowercase()) {
-                return Some(rule); // Return the first matching rule.
+/// * `Option<(&Rule, Vec<String>)>` - The matched `Rule` plus its reflected capture
+///   groups (1-indexed, i.e. group 1 is at index 0), or `None` if nothing matched.
+fn match_rule<'a>(
+    user_input: &str,
+    rules: &'a [Rule],
+    compiled_patterns: &[CompiledPattern<'a>],
+    last_intent: Option<&str>,
+) -> Option<(&'a Rule, Vec<String>)> {
+    let mut context_free_regex: Option<(&Rule, Vec<String>)> = None;
+
+    for compiled in compiled_patterns {
+        if !context_allows(compiled.rule, last_intent) {
+            continue;
+        }
+        if let Some(caps) = compiled.regex.captures(user_input) {
+            let reflected_captures = caps
+                .iter()
+                .skip(1)
+                .map(|group| reflect_fragment(group.map(|m| m.as_str()).unwrap_or("")))
+                .collect();
+            if compiled.rule.context_before.is_some() {
+                return Some((compiled.rule, reflected_captures)); // Contextual match wins immediately.
+            } else if context_free_regex.is_none() {
+                context_free_regex = Some((compiled.rule, reflected_captures));
             }
         }
     }
-    None // No matching intent found.
+
+    // A contextual match in the scoring tier still outranks a context-free
+    // regex hit, so check it before returning `context_free_regex`.
+    let input_tokens: HashSet<&str> = user_input.split_whitespace().collect();
+    if let Some(rule) = best_scoring_match(rules, user_input, &input_tokens, last_intent, true) {
+        return Some((rule, Vec::new()));
+    }
+
+    if context_free_regex.is_some() {
+        return context_free_regex;
+    }
+
+    best_scoring_match(rules, user_input, &input_tokens, last_intent, false).map(|rule| (rule, Vec::new()))
+}
+
+/// Finds the highest-scoring rule (see `score_rule`) above `MATCH_THRESHOLD`.
+///
+/// # Arguments
+///
+/// * `rules` - The rules to evaluate.
+/// * `user_input` - The normalized user input being matched.
+/// * `input_tokens` - `user_input` pre-split on whitespace, for overlap scoring.
+/// * `last_intent` - The previous turn's matched intent, for context gating.
+/// * `contextual_only` - When `true`, only rules with `context_before` are considered;
+///   when `false`, only context-free rules are considered.
+///
+/// # Returns
+///
+/// * `Option<&Rule>` - The best-scoring eligible rule, or `None` if none clears the threshold.
+fn best_scoring_match<'a>(
+    rules: &'a [Rule],
+    user_input: &str,
+    input_tokens: &HashSet<&str>,
+    last_intent: Option<&str>,
+    contextual_only: bool,
+) -> Option<&'a Rule> {
+    let mut best: Option<(&Rule, f64)> = None;
+    for rule in rules {
+        if rule.context_before.is_some() != contextual_only || !context_allows(rule, last_intent) {
+            continue;
+        }
+        let score = score_rule(user_input, input_tokens, rule);
+        if score >= MATCH_THRESHOLD && best.as_ref().is_none_or(|(_, best_score)| score > *best_score) {
+            best = Some((rule, score));
+        }
+    }
+    best.map(|(rule, _)| rule)
+}
+
+/// Scores how well a rule matches the input: the best score across its patterns.
+///
+/// # Arguments
+///
+/// * `user_input` - The normalized user input being matched.
+/// * `input_tokens` - `user_input` pre-split on whitespace.
+/// * `rule` - The rule whose patterns are scored.
+///
+/// # Returns
+///
+/// * `f64` - The highest score among the rule's patterns (`0.0` if it has none).
+fn score_rule(user_input: &str, input_tokens: &HashSet<&str>, rule: &Rule) -> f64 {
+    rule.patterns
+        .iter()
+        .map(|pattern| score_pattern(user_input, input_tokens, pattern))
+        .fold(0.0, f64::max)
+}
+
+/// Scores a single pattern against the input by tokenizing both on whitespace and
+/// computing the overlap ratio (shared tokens / pattern token count), then boosting
+/// whole-pattern and exact-substring matches, which are stronger evidence than
+/// partial token overlap alone. A small tiebreak favors longer patterns, so that
+/// when two patterns both fully match (e.g. `"need help"` and `"need help
+/// immediately"` against the same input), the more specific one wins instead of
+/// whichever happens to come first in the rule list.
+///
+/// # Arguments
+///
+/// * `user_input` - The normalized user input being matched.
+/// * `input_tokens` - `user_input` pre-split on whitespace.
+/// * `pattern` - The rule pattern to score.
+///
+/// # Returns
+///
+/// * `f64` - The pattern's score; `0.0` for an empty pattern.
+fn score_pattern(user_input: &str, input_tokens: &HashSet<&str>, pattern: &str) -> f64 {
+    let pattern_lower = pattern.to_lowercase();
+    let pattern_tokens: Vec<&str> = pattern_lower.split_whitespace().collect();
+    if pattern_tokens.is_empty() {
+        return 0.0;
+    }
+
+    let shared = pattern_tokens.iter().filter(|token| input_tokens.contains(*token)).count();
+    let mut score = shared as f64 / pattern_tokens.len() as f64;
+
+    if score == 1.0 {
+        score += 0.25; // Boost whole-word matches where every pattern token appears in the input.
+    }
+    if user_input.contains(&pattern_lower) {
+        score += 0.5; // Boost exact substring matches.
+    }
+    score += pattern_tokens.len() as f64 * 0.001; // Specificity tiebreak, see doc comment above.
+    score
+}
+
+/// Checks whether a rule is eligible to match given the previous turn's intent.
+///
+/// # Arguments
+///
+/// * `rule` - The rule to check.
+/// * `last_intent` - The previous turn's matched intent, if any.
+///
+/// # Returns
+///
+/// * `bool` - `true` if the rule has no `context_before` requirement, or if
+///   `last_intent` satisfies it.
+fn context_allows(rule: &Rule, last_intent: Option<&str>) -> bool {
+    match &rule.context_before {
+        None => true,
+        Some(required) => last_intent.is_some_and(|intent| required.iter().any(|r| r == intent)),
+    }
 }
 
-/// Generates a dynamic response based on the matched rule and session data.
+/// Generates a dynamic response based on the matched rule, session data, and any
+/// captured (and reflected) fragments from the matching regex.
+///
+/// Picks randomly among `rule.responses` (weighted by `rule.response_weights`
+/// when present) so repeated triggers of the same intent don't always produce
+/// an identical reply.
 ///
 /// # Arguments
 ///
 /// * `rule` - A reference to the matched `Rule` struct.
 /// * `session` - A reference to the current `Session` struct.
+/// * `captures` - Reflected capture groups, substituted into `{1}`, `{2}`, etc.
 ///
 /// # Returns
 ///
 /// * `String` - The generated response with placeholders replaced.
-fn generate_response(rule: &Rule, session: &Session) -> String {
-    let mut response = rule.responses[0].clone(); // Start with the first response template.
+fn generate_response(rule: &Rule, session: &Session, captures: &[String]) -> String {
+    let index = choose_response_index(rule, &mut rand::thread_rng());
+    let mut response = rule.responses[index].clone(); // Start with the chosen response template.
+    for (index, capture) in captures.iter().enumerate() {
+        let placeholder = format!("{{{}}}", index + 1);
+        response = response.replace(&placeholder, capture); // Substitute the reflected capture.
+    }
     response = response.replace("{name}", &session.user_name); // Replace `{name}` with the user's name.
     response = response.replace("{time}", &Local::now().format("%I:%M %p").to_string()); // Replace `{time}` with the current time.
     response // Return the finalized response.
 }
 
+/// Picks which of `rule.responses` to use, weighted by `rule.response_weights`
+/// when it's present and its length matches `responses`; otherwise picks uniformly.
+///
+/// # Arguments
+///
+/// * `rule` - The rule whose response is being chosen.
+/// * `rng` - The random number generator to draw from (injected so tests can seed it).
+///
+/// # Returns
+///
+/// * `usize` - The chosen index into `rule.responses`.
+fn choose_response_index(rule: &Rule, rng: &mut impl Rng) -> usize {
+    if rule.responses.len() <= 1 {
+        return 0;
+    }
+    if let Some(weights) = &rule.response_weights {
+        if weights.len() == rule.responses.len() {
+            if let Ok(distribution) = WeightedIndex::new(weights) {
+                return distribution.sample(rng);
+            }
+        }
+    }
+    rng.gen_range(0..rule.responses.len())
+}
+
 /// Lists all available intents defined in the chatbot's rules.
 ///
 /// # Arguments
@@ -211,6 +849,7 @@ fn list_intents(rules: &[Rule]) {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use rand::SeedableRng;
 
     #[test]
     fn test_clean_input() {
@@ -222,54 +861,301 @@ mod tests {
     #[test]
     fn test_match_rule_found() {
         let rules = vec![
-            Rule {
-                intent: "greet".to_string(),
-                patterns: vec!["hello".to_string(), "hi".to_string()],
-                responses: vec!["Hello, {name}! How can I assist you today?".to_string()],
-            },
-            Rule {
-                intent: "farewell".to_string(),
-                patterns: vec!["bye".to_string(), "goodbye".to_string()],
-                responses: vec!["Goodbye, {name}! Have a great day!".to_string()],
-            },
+            test_rule("greet", &["hello", "hi"], &["Hello, {name}! How can I assist you today?"]),
+            test_rule("farewell", &["bye", "goodbye"], &["Goodbye, {name}! Have a great day!"]),
         ];
+        let compiled_patterns = compile_patterns(&rules);
 
         let input = "hi there";
-        let matched_rule = match_rule(&clean_input(input), &rules);
+        let matched_rule = match_rule(&clean_input(input), &rules, &compiled_patterns, None);
         assert!(matched_rule.is_some());
-        assert_eq!(matched_rule.unwrap().intent, "greet");
+        assert_eq!(matched_rule.unwrap().0.intent, "greet");
     }
 
     #[test]
     fn test_match_rule_not_found() {
-        let rules = vec![
-            Rule {
-                intent: "greet".to_string(),
-                patterns: vec!["hello".to_string(), "hi".to_string()],
-                responses: vec!["Hello, {name}! How can I assist you today?".to_string()],
-            },
-        ];
+        let rules = vec![test_rule(
+            "greet",
+            &["hello", "hi"],
+            &["Hello, {name}! How can I assist you today?"],
+        )];
+        let compiled_patterns = compile_patterns(&rules);
 
         let input = "unknown command";
-        let matched_rule = match_rule(&clean_input(input), &rules);
+        let matched_rule = match_rule(&clean_input(input), &rules, &compiled_patterns, None);
         assert!(matched_rule.is_none());
     }
 
+    #[test]
+    fn test_match_rule_reflection() {
+        let rules = vec![test_rule(
+            "feeling",
+            &["i am (.*)"],
+            &["Why do you say you are {1}?"],
+        )];
+        let compiled_patterns = compile_patterns(&rules);
+
+        let input = clean_input("I am sad");
+        let (rule, captures) = match_rule(&input, &rules, &compiled_patterns, None).unwrap();
+        assert_eq!(rule.intent, "feeling");
+        assert_eq!(captures, vec!["sad".to_string()]);
+    }
+
+    #[test]
+    fn test_match_rule_context_gating() {
+        let mut order_food = test_rule("order_food", &["order food"], &["Sure, what would you like?"]);
+        order_food.sets_context = true;
+        let mut confirm = test_rule("confirm", &["yes"], &["Great, placing your order!"]);
+        confirm.context_before = Some(vec!["order_food".to_string()]);
+        let small_talk = test_rule("small_talk", &["yes"], &["Cool!"]);
+        let rules = vec![order_food, confirm, small_talk];
+        let compiled_patterns = compile_patterns(&rules);
+
+        // Without the right context, "confirm" can't match; the context-free rule wins.
+        let (rule, _) = match_rule(&clean_input("yes"), &rules, &compiled_patterns, None).unwrap();
+        assert_eq!(rule.intent, "small_talk");
+
+        // With "order_food" as the last intent, "confirm" is preferred over "small_talk".
+        let (rule, _) =
+            match_rule(&clean_input("yes"), &rules, &compiled_patterns, Some("order_food")).unwrap();
+        assert_eq!(rule.intent, "confirm");
+    }
+
+    #[test]
+    fn test_match_rule_scoring_is_order_independent() {
+        // Neither pattern appears as a contiguous substring of the input, so the
+        // regex tier can't match either; the scoring fallback has to pick the
+        // rule with the higher token-overlap score regardless of list order.
+        let input = clean_input("i need some help right now fast please");
+
+        let rules_a = vec![
+            test_rule("weaker_match", &["need help immediately"], &["Hang in there!"]),
+            test_rule("stronger_match", &["need help"], &["How can I help?"]),
+        ];
+        let compiled_a = compile_patterns(&rules_a);
+        let (rule_a, _) = match_rule(&input, &rules_a, &compiled_a, None).unwrap();
+
+        let rules_b = vec![
+            test_rule("stronger_match", &["need help"], &["How can I help?"]),
+            test_rule("weaker_match", &["need help immediately"], &["Hang in there!"]),
+        ];
+        let compiled_b = compile_patterns(&rules_b);
+        let (rule_b, _) = match_rule(&input, &rules_b, &compiled_b, None).unwrap();
+
+        assert_eq!(rule_a.intent, "stronger_match");
+        assert_eq!(rule_b.intent, "stronger_match");
+    }
+
+    #[test]
+    fn test_match_rule_literal_patterns_prefer_more_specific_contiguous_match() {
+        // Both patterns are plain literals that appear as contiguous substrings
+        // of the input, so (pre-fix) they'd both hit the regex tier and the
+        // first one compiled would win regardless of fit. Neither should win
+        // just by virtue of list order; the more specific one always should.
+        let input = clean_input("i need help immediately please");
+
+        let rules_a = vec![
+            test_rule("weaker_match", &["need help"], &["How can I help?"]),
+            test_rule("stronger_match", &["need help immediately"], &["Hang in there!"]),
+        ];
+        let compiled_a = compile_patterns(&rules_a);
+        let (rule_a, _) = match_rule(&input, &rules_a, &compiled_a, None).unwrap();
+
+        let rules_b = vec![
+            test_rule("stronger_match", &["need help immediately"], &["Hang in there!"]),
+            test_rule("weaker_match", &["need help"], &["How can I help?"]),
+        ];
+        let compiled_b = compile_patterns(&rules_b);
+        let (rule_b, _) = match_rule(&input, &rules_b, &compiled_b, None).unwrap();
+
+        assert_eq!(rule_a.intent, "stronger_match");
+        assert_eq!(rule_b.intent, "stronger_match");
+    }
+
+    #[test]
+    fn test_match_rule_context_preference_crosses_tiers() {
+        // "confirm" only clears the scoring tier (its pattern isn't a
+        // contiguous substring of the input), while "small_talk" is a literal
+        // substring hit. The contextual rule must still win.
+        let small_talk = test_rule("small_talk", &["good"], &["Cool!"]);
+        let mut confirm = test_rule("confirm", &["good to go"], &["Great, placing your order!"]);
+        confirm.context_before = Some(vec!["order_food".to_string()]);
+        let rules = vec![small_talk, confirm];
+        let compiled_patterns = compile_patterns(&rules);
+
+        let input = clean_input("that sounds good to me");
+        let (rule, _) =
+            match_rule(&input, &rules, &compiled_patterns, Some("order_food")).unwrap();
+        assert_eq!(rule.intent, "confirm");
+    }
+
+    #[test]
+    fn test_choose_response_index_single_response() {
+        let rule = test_rule("greet", &["hi"], &["Hello!"]);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(1);
+        assert_eq!(choose_response_index(&rule, &mut rng), 0);
+    }
+
+    #[test]
+    fn test_choose_response_index_weighted_favors_heavy_option() {
+        let mut rule = test_rule("greet", &["hi"], &["Rare", "Common"]);
+        rule.response_weights = Some(vec![0, 1]);
+        let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+        for _ in 0..20 {
+            assert_eq!(choose_response_index(&rule, &mut rng), 1);
+        }
+    }
+
+    #[test]
+    fn test_choose_response_index_mismatched_weights_falls_back_to_uniform() {
+        let mut rule = test_rule("greet", &["hi"], &["A", "B"]);
+        rule.response_weights = Some(vec![1]); // Wrong length; should fall back to uniform.
+        let mut rng = rand::rngs::StdRng::seed_from_u64(3);
+        let index = choose_response_index(&rule, &mut rng);
+        assert!(index == 0 || index == 1);
+    }
+
     #[test]
     fn test_generate_response() {
-        let rule = Rule {
-            intent: "greet".to_string(),
-            patterns: vec!["hello".to_string()],
-            responses: vec!["Hello, {name}! It's {time}.".to_string()],
-        };
+        let rule = test_rule("greet", &["hello"], &["Hello, {name}! It's {time}."]);
         let session = Session {
             user_id: "user123".to_string(),
             user_name: "Alice".to_string(),
             last_intent: Some("greet".to_string()),
             conversation_history: vec![],
+            attachments: vec![],
         };
-        let response = generate_response(&rule, &session);
+        let response = generate_response(&rule, &session, &[]);
         assert!(response.contains("Alice"));
         assert!(response.contains("It’s"));
     }
+
+    #[test]
+    fn test_strip_prefix_ci() {
+        assert_eq!(strip_prefix_ci("Save Session alice", "save session "), Some("alice"));
+        assert_eq!(strip_prefix_ci("hello", "save session "), None);
+    }
+
+    #[test]
+    fn test_session_store_save_and_load() {
+        let dir = std::env::temp_dir().join("rust_chatbot_test_sessions_save_load");
+        let _ = fs::remove_dir_all(&dir);
+        let store = SessionStore::new(dir.to_str().unwrap()).unwrap();
+
+        let mut session = Session::new("user123", "Alice");
+        session.conversation_history.push("You: hi".to_string());
+        store.save("alice", &session).unwrap();
+
+        let loaded = store.load("alice").unwrap();
+        assert_eq!(loaded.user_name, "Alice");
+        assert_eq!(loaded.conversation_history, vec!["You: hi".to_string()]);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_session_store_list_and_most_recent() {
+        let dir = std::env::temp_dir().join("rust_chatbot_test_sessions_list");
+        let _ = fs::remove_dir_all(&dir);
+        let store = SessionStore::new(dir.to_str().unwrap()).unwrap();
+
+        assert_eq!(store.list().unwrap(), Vec::<String>::new());
+        assert_eq!(store.most_recent().unwrap(), None);
+
+        store.save("first", &Session::new("u", "A")).unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        store.save("second", &Session::new("u", "B")).unwrap();
+
+        let mut names = store.list().unwrap();
+        names.sort();
+        assert_eq!(names, vec!["first".to_string(), "second".to_string()]);
+        assert_eq!(store.most_recent().unwrap(), Some("second".to_string()));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_session_store_rejects_path_traversal_names() {
+        let dir = std::env::temp_dir().join("rust_chatbot_test_sessions_traversal");
+        let _ = fs::remove_dir_all(&dir);
+        let store = SessionStore::new(dir.to_str().unwrap()).unwrap();
+
+        assert!(store.save("../escaped", &Session::new("u", "A")).is_err());
+        assert!(store.save("a/b", &Session::new("u", "A")).is_err());
+        assert!(store.load("../escaped").is_err());
+        assert!(!dir.parent().unwrap().join("escaped.json").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_generate_response_with_reflected_capture() {
+        let rule = test_rule("feeling", &["i am (.*)"], &["Why do you say you are {1}?"]);
+        let session = Session::new("user123", "Alice");
+        let captures = vec!["sad".to_string()];
+        let response = generate_response(&rule, &session, &captures);
+        assert_eq!(response, "Why do you say you are sad?");
+    }
+
+    #[test]
+    fn test_attach_file_text_is_inlined() {
+        let dir = std::env::temp_dir().join("rust_chatbot_test_attach_text");
+        let _ = fs::create_dir_all(&dir);
+        let path = dir.join("notes.txt");
+        fs::write(&path, "remember the milk").unwrap();
+
+        let mut session = Session::new("u", "Alice");
+        let note = attach_file(path.to_str().unwrap(), &mut session).unwrap();
+        assert!(note.contains("remember the milk"));
+        assert!(session.attachments.is_empty()); // Text files aren't fingerprinted.
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_attach_file_binary_is_deduped_by_hash() {
+        let dir = std::env::temp_dir().join("rust_chatbot_test_attach_binary");
+        let _ = fs::create_dir_all(&dir);
+        let path = dir.join("photo.png");
+        fs::write(&path, [0x89, 0x50, 0x4e, 0x47]).unwrap();
+
+        let mut session = Session::new("u", "Alice");
+        attach_file(path.to_str().unwrap(), &mut session).unwrap();
+        assert_eq!(session.attachments.len(), 1);
+
+        // Attaching the same bytes again shouldn't add a second entry.
+        let second_note = attach_file(path.to_str().unwrap(), &mut session).unwrap();
+        assert_eq!(session.attachments.len(), 1);
+        assert!(second_note.contains("already cached"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_assemble_attachments_inline_token() {
+        let dir = std::env::temp_dir().join("rust_chatbot_test_assemble_inline");
+        let _ = fs::create_dir_all(&dir);
+        let path = dir.join("notes.txt");
+        fs::write(&path, "the meeting is at noon").unwrap();
+
+        let mut session = Session::new("u", "Alice");
+        let input = format!("what does @{} say?", path.to_str().unwrap());
+        let assembled = assemble_attachments(&input, &mut session);
+        assert!(assembled.contains("what does"));
+        assert!(assembled.contains("the meeting is at noon"));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Builds a context-free `Rule` for tests, without repeating the context fields everywhere.
+    fn test_rule(intent: &str, patterns: &[&str], responses: &[&str]) -> Rule {
+        Rule {
+            intent: intent.to_string(),
+            patterns: patterns.iter().map(|p| p.to_string()).collect(),
+            responses: responses.iter().map(|r| r.to_string()).collect(),
+            context_before: None,
+            sets_context: true,
+            response_weights: None,
+        }
+    }
 }